@@ -0,0 +1,146 @@
+//! Writes tar archives. Mirrors the `FileSystemImpl`/`FileWriter` split used
+//! for reading, but in reverse: a [`Builder`] wraps a [`std::io::Write`] and
+//! is fed entries one at a time.
+
+use std::io::{Read, Write};
+
+use deku::prelude::*;
+
+use crate::TarHeader;
+
+const BLOCK_SIZE: usize = 512;
+
+/// Per-entry metadata that isn't derived from the path or the body itself.
+#[derive(Debug, Clone)]
+pub struct AppendMetadata {
+    pub mode: u64,
+    pub uid: u64,
+    pub gid: u64,
+    pub mtime: u64,
+    pub uname: String,
+    pub gname: String,
+}
+
+impl Default for AppendMetadata {
+    fn default() -> Self {
+        AppendMetadata {
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            mtime: 0,
+            uname: String::new(),
+            gname: String::new(),
+        }
+    }
+}
+
+/// Writes a tar archive to an underlying [`std::io::Write`].
+pub struct Builder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Builder<W> {
+    pub fn new(writer: W) -> Self {
+        Builder { writer }
+    }
+
+    /// Appends a regular file entry, streaming exactly `size` bytes from `reader`.
+    pub fn append_file(
+        &mut self,
+        path: &str,
+        reader: &mut dyn Read,
+        size: u64,
+        metadata: &AppendMetadata,
+    ) -> Result<(), String> {
+        self.write_header(path, size, b'0', "", metadata)?;
+        self.write_body(reader, size)
+    }
+
+    /// Appends a directory entry.
+    pub fn append_dir(&mut self, path: &str, metadata: &AppendMetadata) -> Result<(), String> {
+        self.write_header(path, 0, b'5', "", metadata)
+    }
+
+    fn write_header(
+        &mut self,
+        path: &str,
+        size: u64,
+        typeflag: u8,
+        linkname: &str,
+        metadata: &AppendMetadata,
+    ) -> Result<(), String> {
+        let header = TarHeader::with_fields(
+            path,
+            metadata.mode,
+            metadata.uid,
+            metadata.gid,
+            size,
+            metadata.mtime,
+            typeflag,
+            linkname,
+            &metadata.uname,
+            &metadata.gname,
+        )?;
+
+        let bytes = header.to_bytes().map_err(|e| e.to_string())?;
+        self.writer.write_all(&bytes).map_err(|e| e.to_string())
+    }
+
+    fn write_body(&mut self, reader: &mut dyn Read, size: u64) -> Result<(), String> {
+        let mut remaining = size;
+        while remaining > 0 {
+            let current_blocksize = if remaining >= BLOCK_SIZE as u64 {
+                BLOCK_SIZE
+            } else {
+                remaining as usize
+            };
+
+            let mut block = [0u8; BLOCK_SIZE];
+            reader
+                .read_exact(&mut block[..current_blocksize])
+                .map_err(|e| e.to_string())?;
+            self.writer.write_all(&block).map_err(|e| e.to_string())?;
+            remaining = remaining.saturating_sub(BLOCK_SIZE as u64);
+        }
+
+        Ok(())
+    }
+
+    /// Writes the two trailing zero blocks that mark the end of the archive.
+    pub fn finish(&mut self) -> Result<(), String> {
+        self.writer
+            .write_all(&[0u8; BLOCK_SIZE * 2])
+            .map_err(|e| e.to_string())
+    }
+}
+
+#[test]
+fn round_trip_test() {
+    use crate::{parse_tar, MemoryFileSystem};
+    use std::io::Cursor;
+
+    let mut archive = Vec::new();
+    {
+        let mut builder = Builder::new(&mut archive);
+        let metadata = AppendMetadata::default();
+        builder
+            .append_file(
+                "hello.txt",
+                &mut Cursor::new(b"hello world".as_slice()),
+                11,
+                &metadata,
+            )
+            .unwrap();
+        builder.append_dir("nested", &metadata).unwrap();
+        builder.finish().unwrap();
+    }
+
+    let fs = MemoryFileSystem::default();
+    parse_tar(&mut Cursor::new(archive), &fs).unwrap();
+
+    let lock = fs.state.lock().unwrap();
+    assert_eq!(lock.len(), 2);
+    assert_eq!(lock[0].name, "hello.txt");
+    assert_eq!(lock[0].data, b"hello world");
+    assert_eq!(lock[1].name, "nested");
+}
@@ -0,0 +1,58 @@
+//! Transparent decompression in front of [`crate::parse_tar`]. Real-world tar
+//! archives are almost always `.tar.gz` or `.tar.zst`; [`parse_tar_auto`] peeks
+//! the first few bytes of the stream to detect that framing and wraps the
+//! reader in the matching streaming decoder before delegating to the regular
+//! block loop. The codecs are gated behind the `gzip`/`zstd` cargo features so
+//! the core parser stays dependency-free for callers who only ever read plain
+//! tar streams.
+
+use std::io::{Chain, Cursor, Read};
+
+use crate::{parse_tar, FileSystemImpl};
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// Parses a tar archive, transparently gunzipping or zstd-decompressing it
+/// first if the stream starts with the matching magic bytes.
+pub fn parse_tar_auto(mut reader: impl Read, fs: &impl FileSystemImpl) -> Result<(), String> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(|e| e.to_string())?;
+    let mut chained: Chain<Cursor<[u8; 4]>, _> = Cursor::new(magic).chain(reader);
+
+    if magic[..2] == GZIP_MAGIC {
+        return parse_gzip(chained, fs);
+    }
+
+    if magic == ZSTD_MAGIC {
+        return parse_zstd(chained, fs);
+    }
+
+    parse_tar(&mut chained, fs)
+}
+
+#[cfg(feature = "gzip")]
+fn parse_gzip<R: Read>(reader: R, fs: &impl FileSystemImpl) -> Result<(), String> {
+    let mut decoder = flate2::read::GzDecoder::new(reader);
+    parse_tar(&mut decoder, fs)
+}
+
+#[cfg(not(feature = "gzip"))]
+fn parse_gzip<R: Read>(_reader: R, _fs: &impl FileSystemImpl) -> Result<(), String> {
+    Err(String::from(
+        "this archive is gzip-compressed, enable the `gzip` feature to read it",
+    ))
+}
+
+#[cfg(feature = "zstd")]
+fn parse_zstd<R: Read>(reader: R, fs: &impl FileSystemImpl) -> Result<(), String> {
+    let mut decoder = zstd::stream::read::Decoder::new(reader).map_err(|e| e.to_string())?;
+    parse_tar(&mut decoder, fs)
+}
+
+#[cfg(not(feature = "zstd"))]
+fn parse_zstd<R: Read>(_reader: R, _fs: &impl FileSystemImpl) -> Result<(), String> {
+    Err(String::from(
+        "this archive is zstd-compressed, enable the `zstd` feature to read it",
+    ))
+}
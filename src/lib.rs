@@ -1,4 +1,5 @@
 use std::{
+    collections::HashMap,
     ffi::OsString,
     io::Write,
     num::ParseIntError,
@@ -8,6 +9,11 @@ use std::{
 
 use deku::prelude::*;
 
+mod pax;
+pub mod builder;
+pub mod compress;
+pub mod sparse;
+
 // {                              /* byte offset */
 //   char name[100];               /*   0 */
 //   char mode[8];                 /* 100 */
@@ -31,12 +37,12 @@ use deku::prelude::*;
 #[deku(endian = "big")]
 pub struct TarHeader {
     name: [u8; 100],
-    mode: u64,
-    uid: u64,
-    gid: u64,
+    mode: [u8; 8],
+    uid: [u8; 8],
+    gid: [u8; 8],
     size: [u8; 12],
     mtime: [u8; 12],
-    chksum: u64,
+    chksum: [u8; 8],
     typeflag: u8,
     linkname: [u8; 100],
     magic: [u8; 6],
@@ -54,10 +60,28 @@ pub struct TarHeaderCastedFields<'a> {
     pub name: &'a str,
     pub uname: &'a str,
     pub gname: &'a str,
+    pub uid: u64,
+    pub gid: u64,
     pub size: u64,
+    /// seconds since the epoch, as a float so PAX sub-second precision survives
+    pub mtime: f64,
+    /// seconds since the epoch; only ever set from a PAX `atime` record, since
+    /// ustar headers have no access-time field of their own
+    pub atime: Option<f64>,
+    pub linkname: &'a str,
     pub typeflag: TarFileType,
 }
 
+/// Controls whether [`parse_tar`] rejects entries whose header checksum
+/// doesn't match what [`TarHeader::validate_checksum`] recomputes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumPolicy {
+    /// reject entries whose checksum doesn't match either interpretation
+    Strict,
+    /// skip checksum validation entirely
+    Lenient,
+}
+
 pub enum TarFileType {
     RegularFile,
     Link,
@@ -69,6 +93,12 @@ pub enum TarFileType {
     ContTypeReserved,
     XHDType,
     XGLType,
+    /// GNU tar extension (`L`): the data block is the real name of the next entry
+    GnuLongName,
+    /// GNU tar extension (`K`): the data block is the real link target of the next entry
+    GnuLongLink,
+    /// GNU tar extension (`S`): a sparse file, whose data is only the non-zero segments
+    GnuSparse,
 }
 
 fn slice_to_str(input: &[u8]) -> Result<&str, String> {
@@ -89,11 +119,83 @@ impl TarHeader {
     pub fn gname(&self) -> Result<&str, String> {
         slice_to_str(&self.gname)
     }
+    pub fn linkname(&self) -> Result<&str, String> {
+        slice_to_str(&self.linkname)
+    }
+    pub fn mode(&self) -> Result<u64, String> {
+        // octal string
+        u64::from_str_radix(slice_to_str(&self.mode)?.trim(), 8)
+            .map_err(|e: ParseIntError| e.to_string())
+    }
+    pub fn uid(&self) -> Result<u64, String> {
+        // octal string
+        u64::from_str_radix(slice_to_str(&self.uid)?.trim(), 8)
+            .map_err(|e: ParseIntError| e.to_string())
+    }
+    pub fn gid(&self) -> Result<u64, String> {
+        // octal string
+        u64::from_str_radix(slice_to_str(&self.gid)?.trim(), 8)
+            .map_err(|e: ParseIntError| e.to_string())
+    }
     pub fn size(&self) -> Result<u64, String> {
         // octal string
         u64::from_str_radix(slice_to_str(&self.size)?, 8).map_err(|e: ParseIntError| e.to_string())
     }
 
+    pub fn mtime(&self) -> Result<u64, String> {
+        // octal string
+        u64::from_str_radix(slice_to_str(&self.mtime)?, 8).map_err(|e: ParseIntError| e.to_string())
+    }
+
+    pub fn chksum(&self) -> Result<u64, String> {
+        // octal string, historically terminated by either "\0 " or " \0"
+        u64::from_str_radix(slice_to_str(&self.chksum)?.trim(), 8)
+            .map_err(|e: ParseIntError| e.to_string())
+    }
+
+    /// Recomputes the classic tar header checksum over `raw_block` (the 512-byte
+    /// block this header was parsed from) and compares it against the stored
+    /// `chksum` field, treating the checksum field itself as eight ASCII spaces
+    /// while summing, as required by the spec. Some historic tars sum the header
+    /// bytes as signed `i8`s instead of unsigned `u8`s, so a match against
+    /// either interpretation is accepted.
+    pub fn validate_checksum(&self, raw_block: &[u8; 512]) -> Result<(), String> {
+        const CHKSUM_FIELD: std::ops::Range<usize> = 148..156;
+
+        let unsigned_sum: u64 = raw_block
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| {
+                if CHKSUM_FIELD.contains(&i) {
+                    b' ' as u64
+                } else {
+                    byte as u64
+                }
+            })
+            .sum();
+
+        let signed_sum: i64 = raw_block
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| {
+                if CHKSUM_FIELD.contains(&i) {
+                    b' ' as i64
+                } else {
+                    (byte as i8) as i64
+                }
+            })
+            .sum();
+
+        let stored = self.chksum()?;
+        if unsigned_sum == stored || signed_sum == stored as i64 {
+            return Ok(());
+        }
+
+        Err(format!(
+            "invalid header checksum: computed {unsigned_sum} (unsigned) / {signed_sum} (signed) but header says {stored}"
+        ))
+    }
+
     pub fn typeflag(&self) -> Result<TarFileType, String> {
         // from GNU docs
         let flag = match self.typeflag {
@@ -107,6 +209,9 @@ impl TarHeader {
             b'7' => TarFileType::ContTypeReserved,
             b'x' => TarFileType::XHDType,
             b'g' => TarFileType::XGLType,
+            b'L' => TarFileType::GnuLongName,
+            b'K' => TarFileType::GnuLongLink,
+            b'S' => TarFileType::GnuSparse,
             _ => return Err(String::from("invalid typeflag header")),
         };
 
@@ -118,7 +223,12 @@ impl TarHeader {
             name: self.name()?,
             uname: self.uname()?,
             gname: self.gname()?,
+            uid: self.uid()?,
+            gid: self.gid()?,
             size: self.size()?,
+            mtime: self.mtime()? as f64,
+            atime: None,
+            linkname: self.linkname()?,
             typeflag: self.typeflag()?,
         })
     }
@@ -131,8 +241,99 @@ impl TarHeader {
 
         Ok(())
     }
+
+    /// Builds a ustar header block for writing, octal-encoding the numeric
+    /// fields and filling in the checksum over the result, so callers don't
+    /// have to hand-populate every byte array themselves.
+    #[allow(clippy::too_many_arguments)]
+    pub fn with_fields(
+        name: &str,
+        mode: u64,
+        uid: u64,
+        gid: u64,
+        size: u64,
+        mtime: u64,
+        typeflag: u8,
+        linkname: &str,
+        uname: &str,
+        gname: &str,
+    ) -> Result<Self, String> {
+        let mut header = TarHeader {
+            name: cstr_field(name)?,
+            mode: octal_field(mode)?,
+            uid: octal_field(uid)?,
+            gid: octal_field(gid)?,
+            size: octal_field(size)?,
+            mtime: octal_field(mtime)?,
+            chksum: *b"        ",
+            typeflag,
+            linkname: cstr_field(linkname)?,
+            magic: *b"ustar\0",
+            version: *b"00",
+            uname: cstr_field(uname)?,
+            gname: cstr_field(gname)?,
+            devmajor: 0,
+            devminor: 0,
+            prefix: [0u8; 155],
+        };
+
+        let bytes = header.to_bytes().map_err(|e| e.to_string())?;
+        let sum: u64 = bytes
+            .iter()
+            .enumerate()
+            .map(|(i, &byte)| {
+                if (148..156).contains(&i) {
+                    b' ' as u64
+                } else {
+                    byte as u64
+                }
+            })
+            .sum();
+        header.chksum = chksum_field(sum);
+
+        Ok(header)
+    }
+}
+
+/// Encodes `s` as a NUL-terminated, NUL-padded fixed-size tar string field.
+fn cstr_field<const N: usize>(s: &str) -> Result<[u8; N], String> {
+    if s.len() >= N {
+        return Err(format!("'{s}' does not fit in a {N}-byte tar field"));
+    }
+
+    let mut field = [0u8; N];
+    field[..s.len()].copy_from_slice(s.as_bytes());
+    Ok(field)
+}
+
+/// Encodes `value` as a NUL-terminated, zero-padded octal tar numeric field.
+/// Errors instead of truncating if `value` needs more than `N - 1` octal digits.
+fn octal_field<const N: usize>(value: u64) -> Result<[u8; N], String> {
+    let max = 8u64.checked_pow((N - 1) as u32).map_or(u64::MAX, |m| m - 1);
+    if value > max {
+        return Err(format!(
+            "value {value} does not fit in a {N}-byte octal tar field"
+        ));
+    }
+
+    let formatted = format!("{:0width$o}\0", value, width = N - 1);
+    let mut field = [0u8; N];
+    field.copy_from_slice(formatted.as_bytes());
+    Ok(field)
+}
+
+/// Encodes the header checksum the way historic tars do: six octal digits,
+/// a NUL, then a trailing space.
+fn chksum_field(sum: u64) -> [u8; 8] {
+    let formatted = format!("{:06o}\0 ", sum);
+    let mut field = [0u8; 8];
+    field.copy_from_slice(formatted.as_bytes());
+    field
 }
 
+/// Lists the entry names of a tar archive, transparently gunzipping or
+/// zstd-decompressing `reader` first if it looks compressed (see
+/// [`compress::parse_tar_auto`]).
 pub fn list_files_in_tar(
     reader: &mut dyn std::io::Read,
 ) -> Result<impl Iterator<Item = String>, String> {
@@ -156,18 +357,70 @@ pub fn list_files_in_tar(
     }
 
     let memory_fs = NullFileSystem::default();
-    parse_tar(reader, &memory_fs)?;
+    compress::parse_tar_auto(reader, &memory_fs)?;
     Ok(FileNameIter {
         fs: memory_fs,
         offset: 0,
     })
 }
 
+/// Reads exactly `size` bytes of entry body from `reader`, consuming the
+/// trailing padding up to the next 512-byte boundary.
+fn read_body(reader: &mut dyn std::io::Read, size: u64) -> Result<Vec<u8>, String> {
+    const BLOCK_SIZE: usize = 512;
+    const BLOCK_SIZE_U64: u64 = BLOCK_SIZE as u64;
+
+    let mut data = Vec::with_capacity(size as usize);
+    let mut remaining = size;
+    while remaining > 0 {
+        let mut block = [0u8; BLOCK_SIZE];
+        reader.read_exact(&mut block).map_err(|e| e.to_string())?;
+        let current_blocksize = if remaining >= BLOCK_SIZE_U64 {
+            BLOCK_SIZE
+        } else {
+            remaining as usize
+        };
+        data.extend_from_slice(&block[..current_blocksize]);
+        remaining = remaining.saturating_sub(BLOCK_SIZE_U64);
+    }
+
+    Ok(data)
+}
+
+/// Writes a sparse file's non-zero segments at their real offsets. `stored` is
+/// the entry's full on-disk body, `start` is where the segment data begins
+/// within it (0, unless a sparse map was embedded ahead of the data).
+fn write_sparse_entries<W: FileWriter>(
+    file: &mut W,
+    stored: &[u8],
+    start: usize,
+    entries: &[sparse::SparseEntry],
+    real_size: u64,
+) -> Result<(), String> {
+    let mut cursor = start;
+    for entry in entries {
+        let length = entry.length as usize;
+        let chunk = stored
+            .get(cursor..cursor + length)
+            .ok_or_else(|| String::from("sparse map references more data than was stored"))?;
+        file.write_block_at(entry.offset, chunk)?;
+        cursor += length;
+    }
+    file.set_final_size(real_size)
+}
+
 pub fn parse_tar(reader: &mut dyn std::io::Read, fs: &impl FileSystemImpl) -> Result<(), String> {
     const BLOCK_SIZE: usize = 512;
     const BLOCK_SIZE_U64: u64 = BLOCK_SIZE as u64;
 
     let mut empty_blocks = 0;
+    // `g` records apply from the point they are read until the end of the archive,
+    // `x` records apply to the single entry that immediately follows them.
+    let mut global_pax: HashMap<String, String> = HashMap::new();
+    let mut pending_pax: Option<HashMap<String, String>> = None;
+    // GNU `L`/`K` entries apply to the single entry that immediately follows them.
+    let mut pending_gnu_long_name: Option<String> = None;
+    let mut pending_gnu_long_link: Option<String> = None;
     loop {
         let mut block = [0u8; BLOCK_SIZE];
         reader.read_exact(&mut block).unwrap();
@@ -184,34 +437,137 @@ pub fn parse_tar(reader: &mut dyn std::io::Read, fs: &impl FileSystemImpl) -> Re
         let tar = TarHeader::try_from(block.as_slice()).unwrap();
 
         tar.validate_magic()?;
-        let casted_fields = tar.casted_fields()?;
+        if fs.checksum_policy() == ChecksumPolicy::Strict {
+            tar.validate_checksum(&block)?;
+        }
+        let typeflag = tar.typeflag()?;
+
+        if matches!(typeflag, TarFileType::XHDType | TarFileType::XGLType) {
+            let data = read_body(reader, tar.size().unwrap())?;
+            let records = pax::parse_records(&data)?;
+            match typeflag {
+                TarFileType::XGLType => global_pax.extend(records),
+                _ => pending_pax = Some(records),
+            }
+            continue;
+        }
+
+        if matches!(typeflag, TarFileType::GnuLongName | TarFileType::GnuLongLink) {
+            let data = read_body(reader, tar.size().unwrap())?;
+            let name = slice_to_str(&data)?.to_owned();
+            match typeflag {
+                TarFileType::GnuLongName => pending_gnu_long_name = Some(name),
+                _ => pending_gnu_long_link = Some(name),
+            }
+            continue;
+        }
+
+        // old-GNU sparse extension blocks sit right after the header, before the
+        // body data, so they must be consumed from `reader` here regardless of
+        // whether any PAX sparse records also apply to this entry.
+        let old_gnu_sparse = if matches!(typeflag, TarFileType::GnuSparse) {
+            Some(sparse::parse_old_gnu_sparse(&block, reader)?)
+        } else {
+            None
+        };
+
+        let mut casted_fields = tar.casted_fields()?;
+        let mut pax_overrides = global_pax.clone();
+        pax_overrides.extend(pending_pax.take().unwrap_or_default());
+        let gnu_long_name = pending_gnu_long_name.take();
+        let gnu_long_link = pending_gnu_long_link.take();
+
+        let owned_name;
+        if let Some(name) = pax_overrides.get("path").cloned().or(gnu_long_name) {
+            owned_name = name;
+            casted_fields.name = &owned_name;
+        }
+        if let Some(size) = pax_overrides.get("size") {
+            casted_fields.size = size
+                .parse()
+                .map_err(|_| String::from("invalid pax size record"))?;
+        }
+        if let Some(mtime) = pax_overrides.get("mtime") {
+            casted_fields.mtime = mtime
+                .parse()
+                .map_err(|_| String::from("invalid pax mtime record"))?;
+        }
+        if let Some(atime) = pax_overrides.get("atime") {
+            casted_fields.atime = Some(
+                atime
+                    .parse()
+                    .map_err(|_| String::from("invalid pax atime record"))?,
+            );
+        }
+        if let Some(uid) = pax_overrides.get("uid") {
+            casted_fields.uid = uid
+                .parse()
+                .map_err(|_| String::from("invalid pax uid record"))?;
+        }
+        if let Some(gid) = pax_overrides.get("gid") {
+            casted_fields.gid = gid
+                .parse()
+                .map_err(|_| String::from("invalid pax gid record"))?;
+        }
+        let owned_linkname;
+        if let Some(linkname) = pax_overrides.get("linkpath").cloned().or(gnu_long_link) {
+            owned_linkname = linkname;
+            casted_fields.linkname = &owned_linkname;
+        }
+        let owned_uname;
+        if let Some(uname) = pax_overrides.get("uname").cloned() {
+            owned_uname = uname;
+            casted_fields.uname = &owned_uname;
+        }
+        let owned_gname;
+        if let Some(gname) = pax_overrides.get("gname").cloned() {
+            owned_gname = gname;
+            casted_fields.gname = &owned_gname;
+        }
 
         let mut file = fs.open(&tar, &casted_fields)?;
 
-        let mut remaining_size = tar.size().unwrap();
-        while remaining_size > 0 {
-            let current_blocksize = if remaining_size >= BLOCK_SIZE_U64 {
-                BLOCK_SIZE_U64
-            } else {
-                remaining_size
-            } as usize;
-            let mut block = [0; BLOCK_SIZE];
-            reader.read_exact(&mut block).unwrap();
-            remaining_size = remaining_size.saturating_sub(BLOCK_SIZE_U64);
-
-            let mut count_zeroes = 0;
-            for i in block {
-                if i == b'\0' {
-                    count_zeroes += 1;
+        if sparse::is_gnu_sparse_1_0(&pax_overrides) {
+            // GNU sparse format 1.0 embeds its map in the entry's own data
+            // stream (padded to a block boundary) instead of in PAX records
+            // or the header, so it must be pulled out of the stored body.
+            let real_size = sparse::pax_real_size(&pax_overrides)?;
+            let stored = read_body(reader, casted_fields.size)?;
+            let (entries, map_len) = sparse::parse_gnu_sparse_1_0_map(&stored)?;
+            write_sparse_entries(&mut file, &stored, map_len, &entries, real_size)?;
+        } else if let Some((entries, real_size)) = match old_gnu_sparse {
+            Some(sparse) => Some(sparse),
+            None => sparse::parse_pax_sparse(&pax_overrides)?,
+        } {
+            // the archive only stores the non-zero segments, back to back
+            let stored = read_body(reader, casted_fields.size)?;
+            write_sparse_entries(&mut file, &stored, 0, &entries, real_size)?;
+        } else {
+            let mut remaining_size = casted_fields.size;
+            while remaining_size > 0 {
+                let current_blocksize = if remaining_size >= BLOCK_SIZE_U64 {
+                    BLOCK_SIZE_U64
                 } else {
-                    if count_zeroes != 0 {
-                        dbg!(count_zeroes);
+                    remaining_size
+                } as usize;
+                let mut block = [0; BLOCK_SIZE];
+                reader.read_exact(&mut block).unwrap();
+                remaining_size = remaining_size.saturating_sub(BLOCK_SIZE_U64);
+
+                let mut count_zeroes = 0;
+                for i in block {
+                    if i == b'\0' {
+                        count_zeroes += 1;
+                    } else {
+                        if count_zeroes != 0 {
+                            dbg!(count_zeroes);
+                        }
+                        count_zeroes = 0;
                     }
-                    count_zeroes = 0;
                 }
+                file.write_block(&block[..current_blocksize])?;
+                dbg!(count_zeroes);
             }
-            file.write_block(&block[..current_blocksize])?;
-            dbg!(count_zeroes);
         }
 
         fs.save(file).unwrap();
@@ -228,6 +584,13 @@ enum ParseTarResult<T> {
 
 pub trait FileSystemImpl {
     type Writer: FileWriter;
+
+    /// How strictly [`parse_tar`] should validate each entry's header checksum.
+    /// Defaults to [`ChecksumPolicy::Strict`].
+    fn checksum_policy(&self) -> ChecksumPolicy {
+        ChecksumPolicy::Strict
+    }
+
     fn open<'a>(
         &self,
         tar_header: &'a TarHeader,
@@ -238,12 +601,47 @@ pub trait FileSystemImpl {
 
 pub trait FileWriter {
     fn write_block(&mut self, data: &[u8]) -> Result<(), String>;
+
+    /// Writes `data` at `offset` instead of appending it. Used to restore sparse
+    /// files, where the stored segments don't start at the beginning of the file.
+    /// The default just appends, which is correct for writers that only ever see
+    /// contiguous data starting at offset 0.
+    fn write_block_at(&mut self, _offset: u64, data: &[u8]) -> Result<(), String> {
+        self.write_block(data)
+    }
+
+    /// Sets the final length of the file, so that a sparse file's trailing hole
+    /// (beyond the last written segment) is represented. Default is a no-op.
+    fn set_final_size(&mut self, _size: u64) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "filesystem", unix))]
+use std::os::unix::fs::PermissionsExt;
+
+#[cfg(feature = "filesystem")]
+use std::io::Seek;
+
+/// Controls what happens when an entry name would traverse above `start_folder`
+/// (a leading `/`, or enough `..` components to escape the root).
+#[cfg(feature = "filesystem")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathSanitization {
+    /// refuse the entry with an error
+    StrictReject,
+    /// strip the leading `/` and drop any `..` that would escape the root, then continue
+    SanitizeAndContinue,
 }
 
 #[cfg(feature = "filesystem")]
 pub struct FileSystem {
     /// sets the gid uid in the tar file, false will just use the current user
     pub use_metadata: bool,
+    /// how to handle entry names that try to escape `start_folder`
+    pub path_sanitization: PathSanitization,
+    /// how strictly to validate each entry's header checksum
+    pub checksum_policy: ChecksumPolicy,
     start_folder: cap_std::fs::Dir,
 }
 
@@ -252,6 +650,8 @@ impl std::default::Default for FileSystem {
     fn default() -> Self {
         Self {
             use_metadata: false,
+            path_sanitization: PathSanitization::StrictReject,
+            checksum_policy: ChecksumPolicy::Strict,
             start_folder: cap_std::fs::Dir::open_ambient_dir(".", cap_std::ambient_authority())
                 .unwrap(),
         }
@@ -268,64 +668,359 @@ impl FileSystem {
             )
             .unwrap(),
             use_metadata: false,
+            path_sanitization: PathSanitization::StrictReject,
+            checksum_policy: ChecksumPolicy::Strict,
+        }
+    }
+}
+
+/// Normalizes a tar entry name relative to the archive root: strips any
+/// leading `/`, drops `.` components, and resolves `..` components against
+/// the path built up so far. Returns an error if the name is empty after
+/// normalization, or (depending on `policy`) if it tries to escape the root.
+#[cfg(feature = "filesystem")]
+fn sanitize_path(name: &str, policy: PathSanitization) -> Result<String, String> {
+    if policy == PathSanitization::StrictReject && name.starts_with('/') {
+        return Err(format!("entry name '{name}' is an absolute path"));
+    }
+
+    let mut components: Vec<&str> = Vec::new();
+    for component in name.trim_start_matches('/').split('/') {
+        match component {
+            "" | "." => continue,
+            ".." => match (components.pop(), policy) {
+                (Some(_), _) => {}
+                (None, PathSanitization::StrictReject) => {
+                    return Err(format!(
+                        "entry name '{name}' traverses above the archive root"
+                    ))
+                }
+                (None, PathSanitization::SanitizeAndContinue) => {}
+            },
+            other => components.push(other),
         }
     }
+
+    if components.is_empty() {
+        return Err(format!("entry name '{name}' resolves to the archive root"));
+    }
+
+    Ok(components.join("/"))
+}
+
+#[cfg(feature = "filesystem")]
+#[test]
+fn sanitize_path_test() {
+    assert_eq!(
+        sanitize_path("archive/lorem.txt", PathSanitization::StrictReject).unwrap(),
+        "archive/lorem.txt"
+    );
+    assert!(sanitize_path("../evil", PathSanitization::StrictReject).is_err());
+    assert!(sanitize_path("/etc/passwd", PathSanitization::StrictReject).is_err());
+
+    assert_eq!(
+        sanitize_path("../evil", PathSanitization::SanitizeAndContinue).unwrap(),
+        "evil"
+    );
+    assert_eq!(
+        sanitize_path("/etc/passwd", PathSanitization::SanitizeAndContinue).unwrap(),
+        "etc/passwd"
+    );
+}
+
+#[cfg(feature = "filesystem")]
+#[test]
+fn strict_reject_keeps_traversal_entries_out_of_the_target_dir_test() {
+    use crate::builder::{AppendMetadata, Builder};
+    use std::io::Cursor;
+
+    // the attack entries come first, so StrictReject must bail before either
+    // one touches the filesystem
+    let mut archive = Vec::new();
+    let mut builder = Builder::new(&mut archive);
+    let metadata = AppendMetadata::default();
+    builder
+        .append_file("../evil", &mut Cursor::new(b"pwned".as_slice()), 5, &metadata)
+        .unwrap();
+    builder
+        .append_file("/etc/passwd", &mut Cursor::new(b"pwned".as_slice()), 5, &metadata)
+        .unwrap();
+    builder.finish().unwrap();
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut fs = FileSystem::new(tmp_dir.path());
+    fs.path_sanitization = PathSanitization::StrictReject;
+
+    assert!(parse_tar(&mut Cursor::new(archive), &fs).is_err());
+    assert_eq!(std::fs::read_dir(tmp_dir.path()).unwrap().count(), 0);
+}
+
+#[cfg(feature = "filesystem")]
+#[test]
+fn sanitize_and_continue_confines_traversal_entries_to_the_target_dir_test() {
+    use crate::builder::{AppendMetadata, Builder};
+    use std::io::Cursor;
+
+    // "etc" has to exist before "/etc/passwd" can be created under it, same as
+    // any other nested path in a real-world archive
+    let mut archive = Vec::new();
+    let mut builder = Builder::new(&mut archive);
+    let metadata = AppendMetadata::default();
+    builder.append_dir("etc", &metadata).unwrap();
+    builder
+        .append_file("../evil", &mut Cursor::new(b"pwned".as_slice()), 5, &metadata)
+        .unwrap();
+    builder
+        .append_file("/etc/passwd", &mut Cursor::new(b"pwned".as_slice()), 5, &metadata)
+        .unwrap();
+    builder.finish().unwrap();
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let mut fs = FileSystem::new(tmp_dir.path());
+    fs.path_sanitization = PathSanitization::SanitizeAndContinue;
+
+    parse_tar(&mut Cursor::new(archive), &fs).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("evil")).unwrap(),
+        "pwned"
+    );
+    assert_eq!(
+        std::fs::read_to_string(tmp_dir.path().join("etc/passwd")).unwrap(),
+        "pwned"
+    );
+}
+
+#[cfg(feature = "filesystem")]
+#[test]
+fn gnu_sparse_old_format_writes_through_filesystem_test() {
+    use std::io::Cursor;
+
+    // `with_fields` only builds dense ustar headers, so the old-GNU sparse
+    // table (typeflag `S`) has to be patched into the raw block by hand:
+    // two entries (5 bytes at offset 0, 5 bytes at offset 10) expanding to a
+    // 20-byte real file, with 10 bytes of non-zero data actually stored.
+    let header =
+        TarHeader::with_fields("sparse-old.bin", 0o644, 0, 0, 10, 0, b'S', "", "", "").unwrap();
+    let mut block = header.to_bytes().unwrap();
+
+    block[386..398].copy_from_slice(&octal_field::<12>(0).unwrap());
+    block[398..410].copy_from_slice(&octal_field::<12>(5).unwrap());
+    block[410..422].copy_from_slice(&octal_field::<12>(10).unwrap());
+    block[422..434].copy_from_slice(&octal_field::<12>(5).unwrap());
+    block[482] = 0; // not extended, the 4 in-header slots are enough
+    block[483..495].copy_from_slice(&octal_field::<12>(20).unwrap());
+
+    // the sparse table patch invalidates the checksum `with_fields` computed
+    let sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u64 } else { b as u64 })
+        .sum();
+    block[148..156].copy_from_slice(&chksum_field(sum));
+
+    let mut archive = block;
+    archive.extend_from_slice(b"AAAAABBBBB");
+    archive.resize(archive.len() + (512 - 10), 0);
+    archive.extend_from_slice(&[0u8; 512 * 2]);
+
+    let tmp_dir = tempfile::tempdir().unwrap();
+    let fs = FileSystem::new(tmp_dir.path());
+    parse_tar(&mut Cursor::new(archive), &fs).unwrap();
+
+    let written = std::fs::read(tmp_dir.path().join("sparse-old.bin")).unwrap();
+    assert_eq!(written.len(), 20);
+    assert_eq!(&written[0..5], b"AAAAA");
+    assert_eq!(&written[5..10], &[0u8; 5]);
+    assert_eq!(&written[10..15], b"BBBBB");
+    assert_eq!(&written[15..20], &[0u8; 5]);
 }
 
 #[cfg(feature = "filesystem")]
 impl FileSystemImpl for FileSystem {
     type Writer = FileWrapper;
 
+    fn checksum_policy(&self) -> ChecksumPolicy {
+        self.checksum_policy
+    }
+
     fn open<'a>(
         &self,
         tar_header: &'a TarHeader,
         casted_fields: &'a TarHeaderCastedFields,
     ) -> Result<Self::Writer, String> {
-        if self.use_metadata {
-            todo!("implement setting the user of the file")
-        } else {
-            match casted_fields.typeflag {
-                TarFileType::RegularFile => self
-                    .start_folder
-                    .create(casted_fields.name)
-                    .map_err(|e| e.to_string())
-                    .map(|x| FileWrapper::File(x)),
-                TarFileType::Dir => self
-                    .start_folder
-                    .create_dir_all(casted_fields.name)
-                    .map_err(|e| e.to_string())
-                    .map(|_| FileWrapper::Dir),
-                _ => Err(String::from(
-                    "unable to create file with type other than file or dir",
-                )),
+        let name = sanitize_path(casted_fields.name, self.path_sanitization)?;
+
+        let kind = match casted_fields.typeflag {
+            // a sparse file's content still lands in a regular file - only how
+            // its data blocks are written (see `write_sparse_entries`) differs
+            TarFileType::RegularFile | TarFileType::GnuSparse => self
+                .start_folder
+                .create(&name)
+                .map(FileWrapperKind::File)
+                .map_err(|e| e.to_string())?,
+            TarFileType::Dir => self
+                .start_folder
+                .create_dir_all(&name)
+                .map(|_| FileWrapperKind::Dir)
+                .map_err(|e| e.to_string())?,
+            TarFileType::Link => {
+                let target = sanitize_path(casted_fields.linkname, self.path_sanitization)?;
+                self.start_folder
+                    .hard_link(&target, &self.start_folder, &name)
+                    .map_err(|e| e.to_string())?;
+                FileWrapperKind::Link
             }
-            // let dir = cap_std::fs::Dir::open_ambient_dir(&self.start_folder, self.ambient_authority);
-            // let path = self.start_folder.join(casted_fields.name);
-            // we probably need to make this safe, if name has .. or absolute path, it goes there.
-            // std::fs::File::create(path).map_err(|e| e.to_string())
-        }
+            TarFileType::SymTypeReserved => {
+                // the symlink's target is not constrained to `start_folder` - that is
+                // normal symlink behavior, only where the link itself is created matters here
+                self.start_folder
+                    .symlink(casted_fields.linkname, &name)
+                    .map_err(|e| e.to_string())?;
+                FileWrapperKind::Symlink
+            }
+            _ => {
+                return Err(String::from(
+                    "unable to create file with type other than file, dir, link or symlink",
+                ))
+            }
+        };
+
+        // times are applied in `save`, after the body has been written, so that
+        // streaming the file content doesn't bump `mtime` back to "now"
+        let metadata = self.use_metadata.then(|| EntryMetadata {
+            name: name.clone(),
+            mode: tar_header.mode().unwrap_or(0),
+            mtime: casted_fields.mtime,
+            atime: casted_fields.atime,
+            uid: casted_fields.uid,
+            gid: casted_fields.gid,
+        });
+
+        Ok(FileWrapper { kind, metadata })
     }
 
     fn save(&self, writer: Self::Writer) -> Result<(), String> {
-        match writer {
-            FileWrapper::File(mut writer) => Ok(writer.flush().map_err(|e| e.to_string())?),
-            FileWrapper::Dir => Ok(()),
+        let FileWrapper { mut kind, metadata } = writer;
+
+        if let FileWrapperKind::File(file) = &mut kind {
+            file.flush().map_err(|e| e.to_string())?;
+        }
+
+        if let Some(metadata) = metadata {
+            self.apply_metadata(&kind, &metadata)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "filesystem")]
+impl FileSystem {
+    fn apply_metadata(&self, kind: &FileWrapperKind, metadata: &EntryMetadata) -> Result<(), String> {
+        // symlinks carry no independent permissions/times worth restoring here
+        if matches!(kind, FileWrapperKind::Symlink) {
+            return Ok(());
         }
+
+        self.start_folder
+            .set_permissions(
+                &metadata.name,
+                cap_std::fs::Permissions::from_std(std::fs::Permissions::from_mode(
+                    metadata.mode as u32,
+                )),
+            )
+            .map_err(|e| e.to_string())?;
+
+        let to_system_time_spec = |seconds_since_epoch: f64| {
+            let seconds = seconds_since_epoch.trunc() as i64;
+            let nanoseconds = (seconds_since_epoch.fract() * 1_000_000_000.0) as u32;
+            cap_std::time::SystemTimeSpec::from_std(
+                std::time::SystemTime::UNIX_EPOCH
+                    + std::time::Duration::new(seconds as u64, nanoseconds),
+            )
+        };
+        let mtime = to_system_time_spec(metadata.mtime);
+        let atime = metadata.atime.map_or(mtime, to_system_time_spec);
+        self.start_folder
+            .set_times(&metadata.name, Some(atime), Some(mtime))
+            .map_err(|e| e.to_string())?;
+
+        if let FileWrapperKind::File(file) = kind {
+            apply_ownership(file, metadata.uid, metadata.gid)?;
+        }
+
+        Ok(())
     }
 }
 
+/// Applies the owning uid/gid to an already-open file, tolerating a lack of
+/// permission (only root can usually `chown` to an arbitrary uid/gid).
+#[cfg(all(feature = "filesystem", unix))]
+fn apply_ownership(file: &cap_std::fs::File, uid: u64, gid: u64) -> Result<(), String> {
+    use rustix::fd::AsFd;
+    use rustix::fs::{Gid, Uid};
+
+    match rustix::fs::fchown(file.as_fd(), Some(Uid::from_raw(uid as u32)), Some(Gid::from_raw(gid as u32))) {
+        Ok(()) | Err(rustix::io::Errno::PERM) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(all(feature = "filesystem", not(unix)))]
+fn apply_ownership(_file: &cap_std::fs::File, _uid: u64, _gid: u64) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(feature = "filesystem")]
+struct EntryMetadata {
+    name: String,
+    mode: u64,
+    mtime: f64,
+    /// from a PAX `atime` record, if the entry had one; falls back to `mtime` otherwise
+    atime: Option<f64>,
+    uid: u64,
+    gid: u64,
+}
+
 #[cfg(feature = "filesystem")]
-pub enum FileWrapper {
+pub struct FileWrapper {
+    kind: FileWrapperKind,
+    metadata: Option<EntryMetadata>,
+}
+
+#[cfg(feature = "filesystem")]
+pub enum FileWrapperKind {
     File(cap_std::fs::File),
     Dir,
+    Link,
+    Symlink,
 }
 
 #[cfg(feature = "filesystem")]
 impl FileWriter for FileWrapper {
     fn write_block(&mut self, data: &[u8]) -> Result<(), String> {
-        match self {
-            FileWrapper::File(file) => file.write_all(data).map_err(|e| e.to_string()),
-            FileWrapper::Dir => Err(String::from("unable to write to dir type")),
+        match &mut self.kind {
+            FileWrapperKind::File(file) => file.write_all(data).map_err(|e| e.to_string()),
+            _ => Err(String::from("unable to write to this entry's type")),
+        }
+    }
+
+    fn write_block_at(&mut self, offset: u64, data: &[u8]) -> Result<(), String> {
+        match &mut self.kind {
+            FileWrapperKind::File(file) => {
+                file.seek(std::io::SeekFrom::Start(offset))
+                    .map_err(|e| e.to_string())?;
+                file.write_all(data).map_err(|e| e.to_string())
+            }
+            _ => Err(String::from("unable to write to this entry's type")),
+        }
+    }
+
+    fn set_final_size(&mut self, size: u64) -> Result<(), String> {
+        match &mut self.kind {
+            FileWrapperKind::File(file) => file.set_len(size).map_err(|e| e.to_string()),
+            _ => Ok(()),
         }
     }
 }
@@ -410,6 +1105,255 @@ impl<'a> FileSystemImpl for NullFileSystem {
     }
 }
 
+#[test]
+fn octal_field_rejects_value_too_large_for_field_test() {
+    // a 12-byte octal field (11 digits + NUL) tops out at 8 GiB - 1
+    assert!(octal_field::<12>(8 * 1024 * 1024 * 1024 - 1).is_ok());
+    assert!(octal_field::<12>(8 * 1024 * 1024 * 1024).is_err());
+}
+
+#[test]
+fn build_rejects_file_too_large_for_size_field_test() {
+    let sixty_four_gib = 64 * 1024 * 1024 * 1024;
+    assert!(TarHeader::with_fields(
+        "big.bin", 0o644, 0, 0, sixty_four_gib, 0, b'0', "", "", ""
+    )
+    .is_err());
+}
+
+#[test]
+fn validate_checksum_accepts_unsigned_and_signed_sum_test() {
+    let header = TarHeader::with_fields(
+        "high-byte.txt",
+        0o644,
+        0,
+        0,
+        0,
+        0,
+        b'0',
+        "",
+        // placeholder uname byte (0x7f, no high bit); it is overwritten below
+        "\u{7f}",
+        "",
+    )
+    .unwrap();
+    let mut block = [0u8; 512];
+    block.copy_from_slice(&header.to_bytes().unwrap());
+    // corrupt the uname field with a genuinely non-ASCII byte (0xff), which the
+    // builder above can't express directly since it only accepts `&str`
+    block[265] = 0xff;
+
+    let unsigned_sum: u64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| if (148..156).contains(&i) { b' ' as u64 } else { byte as u64 })
+        .sum();
+    let signed_sum: i64 = block
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            if (148..156).contains(&i) {
+                b' ' as i64
+            } else {
+                (byte as i8) as i64
+            }
+        })
+        .sum();
+    assert_ne!(unsigned_sum as i64, signed_sum, "test fixture must exercise differing sums");
+
+    let unsigned_header = {
+        let mut h = TarHeader::try_from(block.as_slice()).unwrap();
+        h.chksum = chksum_field(unsigned_sum);
+        h
+    };
+    let mut unsigned_block = block;
+    unsigned_block[148..156].copy_from_slice(&unsigned_header.chksum);
+    unsigned_header.validate_checksum(&unsigned_block).unwrap();
+
+    let signed_header = {
+        let mut h = TarHeader::try_from(block.as_slice()).unwrap();
+        h.chksum = chksum_field(signed_sum as u64);
+        h
+    };
+    let mut signed_block = block;
+    signed_block[148..156].copy_from_slice(&signed_header.chksum);
+    signed_header.validate_checksum(&signed_block).unwrap();
+}
+
+#[test]
+fn pax_overrides_uid_gid_uname_gname_atime_test() {
+    use std::io::Cursor;
+
+    #[derive(Default)]
+    struct CapturingFs {
+        captured: Mutex<Option<(u64, u64, String, String, Option<f64>)>>,
+    }
+
+    struct CapturingFile;
+    impl FileWriter for CapturingFile {
+        fn write_block(&mut self, _data: &[u8]) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    impl FileSystemImpl for CapturingFs {
+        type Writer = CapturingFile;
+        fn open(
+            &self,
+            _tar: &TarHeader,
+            casted_fields: &TarHeaderCastedFields,
+        ) -> Result<Self::Writer, String> {
+            *self.captured.lock().unwrap() = Some((
+                casted_fields.uid,
+                casted_fields.gid,
+                casted_fields.uname.to_owned(),
+                casted_fields.gname.to_owned(),
+                casted_fields.atime,
+            ));
+            Ok(CapturingFile)
+        }
+        fn save(&self, _writer: Self::Writer) -> Result<(), String> {
+            Ok(())
+        }
+    }
+
+    fn pax_record(key: &str, value: &str) -> String {
+        let content = format!("{key}={value}\n");
+        let mut len = content.len() + 2;
+        loop {
+            let candidate = len.to_string().len() + 1 + content.len();
+            if candidate == len {
+                break;
+            }
+            len = candidate;
+        }
+        format!("{len} {content}")
+    }
+
+    fn pad_block(mut data: Vec<u8>) -> Vec<u8> {
+        let padded_len = (data.len() + 511) / 512 * 512;
+        data.resize(padded_len, 0);
+        data
+    }
+
+    let pax_body = format!(
+        "{}{}{}{}{}",
+        pax_record("uid", "70000"),
+        pax_record("gid", "70001"),
+        pax_record("uname", "nonascii-öwner"),
+        pax_record("gname", "nonascii-group"),
+        pax_record("atime", "1700000000.5"),
+    )
+    .into_bytes();
+
+    let pax_header = TarHeader::with_fields(
+        "pax-header",
+        0o644,
+        0,
+        0,
+        pax_body.len() as u64,
+        0,
+        b'x',
+        "",
+        "",
+        "",
+    )
+    .unwrap();
+
+    let entry_header = TarHeader::with_fields(
+        "owned.txt", 0o644, 0, 0, 0, 0, b'0', "", "orig-user", "orig-group",
+    )
+    .unwrap();
+
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&pax_header.to_bytes().unwrap());
+    archive.extend_from_slice(&pad_block(pax_body));
+    archive.extend_from_slice(&entry_header.to_bytes().unwrap());
+    archive.extend_from_slice(&[0u8; 512 * 2]);
+
+    let fs = CapturingFs::default();
+    parse_tar(&mut Cursor::new(archive), &fs).unwrap();
+
+    let (uid, gid, uname, gname, atime) = fs.captured.lock().unwrap().clone().unwrap();
+    assert_eq!(uid, 70000);
+    assert_eq!(gid, 70001);
+    assert_eq!(uname, "nonascii-öwner");
+    assert_eq!(gname, "nonascii-group");
+    assert_eq!(atime, Some(1700000000.5));
+}
+
+#[test]
+fn parse_tar_handles_gnu_sparse_format_1_0_test() {
+    use std::io::Cursor;
+
+    fn pax_record(key: &str, value: &str) -> String {
+        let content = format!("{key}={value}\n");
+        let mut len = content.len() + 2;
+        loop {
+            let candidate = len.to_string().len() + 1 + content.len();
+            if candidate == len {
+                break;
+            }
+            len = candidate;
+        }
+        format!("{len} {content}")
+    }
+
+    fn pad_block(mut data: Vec<u8>) -> Vec<u8> {
+        let padded_len = (data.len() + 511) / 512 * 512;
+        data.resize(padded_len, 0);
+        data
+    }
+
+    let pax_body = format!(
+        "{}{}{}",
+        pax_record("GNU.sparse.major", "1"),
+        pax_record("GNU.sparse.minor", "0"),
+        pax_record("GNU.sparse.realsize", "20"),
+    )
+    .into_bytes();
+
+    let pax_header = TarHeader::with_fields(
+        "pax-header",
+        0o644,
+        0,
+        0,
+        pax_body.len() as u64,
+        0,
+        b'x',
+        "",
+        "",
+        "",
+    )
+    .unwrap();
+
+    // the sparse map ("2 entries, offset 0 len 5, offset 10 len 5") lives at
+    // the start of the entry's own data, padded to a block boundary, followed
+    // by the two 5-byte segments it describes
+    let map_block = pad_block(b"2\n0\n5\n10\n5\n".to_vec());
+    let mut body = map_block.clone();
+    body.extend_from_slice(&pad_block(b"AAAAABBBBB".to_vec()));
+    let entry_size = (map_block.len() + 10) as u64;
+
+    let entry_header =
+        TarHeader::with_fields("sparse.bin", 0o644, 0, 0, entry_size, 0, b'0', "", "", "").unwrap();
+
+    let mut archive = Vec::new();
+    archive.extend_from_slice(&pax_header.to_bytes().unwrap());
+    archive.extend_from_slice(&pad_block(pax_body));
+    archive.extend_from_slice(&entry_header.to_bytes().unwrap());
+    archive.extend_from_slice(&body);
+    archive.extend_from_slice(&[0u8; 512 * 2]);
+
+    let fs = MemoryFileSystem::default();
+    parse_tar(&mut Cursor::new(archive), &fs).unwrap();
+
+    let lock = fs.state.lock().unwrap();
+    assert_eq!(lock.len(), 1);
+    assert_eq!(lock[0].name, "sparse.bin");
+    assert_eq!(lock[0].data, b"AAAAABBBBB");
+}
+
 #[test]
 fn xdxd_test() {
     use std::fs::File;
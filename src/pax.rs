@@ -0,0 +1,67 @@
+//! PAX extended header support (POSIX.1-2001, typeflags `x` and `g`).
+//!
+//! A PAX header entry stores its payload as a sequence of records of the form
+//! `"<length> <key>=<value>\n"`, where `<length>` is the decimal byte count of
+//! the whole record (including the length field itself and the trailing
+//! newline). The records that follow apply to the single next entry (`x`) or
+//! to every entry for the rest of the archive (`g`).
+
+use std::collections::HashMap;
+
+/// Parses the record stream stored in the body of an `x`/`g` typeflag entry.
+pub fn parse_records(mut data: &[u8]) -> Result<HashMap<String, String>, String> {
+    let mut records = HashMap::new();
+
+    while !data.is_empty() {
+        let space = data
+            .iter()
+            .position(|&b| b == b' ')
+            .ok_or_else(|| String::from("invalid pax record: missing length"))?;
+
+        let len_str = std::str::from_utf8(&data[..space]).map_err(|e| e.to_string())?;
+        let len: usize = len_str
+            .parse()
+            .map_err(|_| String::from("invalid pax record: length is not a number"))?;
+
+        if len == 0 || len > data.len() {
+            return Err(String::from("invalid pax record: length out of bounds"));
+        }
+        if space + 1 >= len {
+            return Err(String::from("invalid pax record: length too short for value"));
+        }
+
+        let record = &data[..len];
+        // strip the "<length> " prefix and the trailing '\n'
+        let body = &record[space + 1..record.len() - 1];
+        let eq = body
+            .iter()
+            .position(|&b| b == b'=')
+            .ok_or_else(|| String::from("invalid pax record: missing '='"))?;
+
+        let key = std::str::from_utf8(&body[..eq]).map_err(|e| e.to_string())?;
+        let value = std::str::from_utf8(&body[eq + 1..]).map_err(|e| e.to_string())?;
+        records.insert(key.to_owned(), value.to_owned());
+
+        data = &data[len..];
+    }
+
+    Ok(records)
+}
+
+#[test]
+fn parse_records_test() {
+    let data = b"16 path=foo/bar\n22 mtime=1700000000.5\n";
+    let records = parse_records(data).unwrap();
+    assert_eq!(records.get("path").unwrap(), "foo/bar");
+    assert_eq!(records.get("mtime").unwrap(), "1700000000.5");
+}
+
+#[test]
+fn parse_records_rejects_garbage_test() {
+    assert!(parse_records(b"not a pax record").is_err());
+}
+
+#[test]
+fn parse_records_rejects_length_shorter_than_separator_test() {
+    assert!(parse_records(b"2 \n").is_err());
+}
@@ -0,0 +1,270 @@
+//! GNU sparse file support. Sparse archives only store the non-zero data
+//! segments of a file plus a map of where each segment belongs in the real
+//! (fully expanded) file. The map can come from either the old GNU header
+//! extension area (typeflag `S`, continued via extra 512-byte blocks when
+//! `isextended` is set) or from PAX `GNU.sparse.*` records.
+
+use std::collections::HashMap;
+
+/// One non-zero segment of a sparse file: `length` bytes belong at `offset`
+/// in the fully expanded file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SparseEntry {
+    pub offset: u64,
+    pub length: u64,
+}
+
+fn octal_field(bytes: &[u8]) -> Result<u64, String> {
+    let s = std::str::from_utf8(bytes).map_err(|e| e.to_string())?;
+    let s = s.trim_matches(|c: char| c == '\0' || c == ' ');
+    if s.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(s, 8).map_err(|e| e.to_string())
+}
+
+/// Parses the old GNU sparse format: up to 4 `(offset, numbytes)` pairs embedded
+/// in the header block itself, continued through additional 512-byte extension
+/// blocks (21 pairs each) while the block's `isextended` byte is set.
+pub fn parse_old_gnu_sparse(
+    block: &[u8; 512],
+    reader: &mut dyn std::io::Read,
+) -> Result<(Vec<SparseEntry>, u64), String> {
+    const SPARSE_TABLE_OFFSET: usize = 386;
+    const ENTRY_SIZE: usize = 24;
+    const IS_EXTENDED_OFFSET: usize = 482;
+    const REALSIZE_OFFSET: usize = 483;
+
+    let mut entries = Vec::new();
+    for i in 0..4 {
+        let base = SPARSE_TABLE_OFFSET + i * ENTRY_SIZE;
+        push_entry(&mut entries, &block[base..base + 12], &block[base + 12..base + 24])?;
+    }
+
+    let real_size = octal_field(&block[REALSIZE_OFFSET..REALSIZE_OFFSET + 12])?;
+
+    let mut is_extended = block[IS_EXTENDED_OFFSET] != 0;
+    while is_extended {
+        let mut extension = [0u8; 512];
+        reader.read_exact(&mut extension).map_err(|e| e.to_string())?;
+
+        for i in 0..21 {
+            let base = i * ENTRY_SIZE;
+            push_entry(
+                &mut entries,
+                &extension[base..base + 12],
+                &extension[base + 12..base + 24],
+            )?;
+        }
+
+        is_extended = extension[504] != 0;
+    }
+
+    Ok((entries, real_size))
+}
+
+fn push_entry(entries: &mut Vec<SparseEntry>, offset: &[u8], length: &[u8]) -> Result<(), String> {
+    let offset = octal_field(offset)?;
+    let length = octal_field(length)?;
+    if offset != 0 || length != 0 {
+        entries.push(SparseEntry { offset, length });
+    }
+    Ok(())
+}
+
+/// Whether these PAX records mark a GNU sparse format 1.0 entry. Unlike
+/// formats 0.0/0.1, 1.0's sparse map isn't stored in PAX records at all - it's
+/// embedded at the start of the entry's own data stream, so callers must use
+/// [`parse_gnu_sparse_1_0_map`] on the entry body instead of [`parse_pax_sparse`].
+pub fn is_gnu_sparse_1_0(records: &HashMap<String, String>) -> bool {
+    records.get("GNU.sparse.major").map(String::as_str) == Some("1")
+}
+
+pub(crate) fn pax_real_size(records: &HashMap<String, String>) -> Result<u64, String> {
+    records
+        .get("GNU.sparse.size")
+        .or_else(|| records.get("GNU.sparse.realsize"))
+        .ok_or_else(|| String::from("GNU sparse pax entry is missing a real size record"))?
+        .parse()
+        .map_err(|_| String::from("invalid GNU.sparse.size record"))
+}
+
+/// Parses a sparse map out of PAX extended records, if the entry has one.
+/// Supports the `GNU.sparse.map` single-record form (`"offset,length,..."`)
+/// and the numbered `GNU.sparse.offset.N`/`GNU.sparse.numbytes.N` form (GNU
+/// sparse formats 0.1 and 0.0). Format 1.0 entries must be handled via
+/// [`is_gnu_sparse_1_0`]/[`parse_gnu_sparse_1_0_map`] instead; this function
+/// errors rather than silently returning an empty map if it sees one.
+pub fn parse_pax_sparse(
+    records: &HashMap<String, String>,
+) -> Result<Option<(Vec<SparseEntry>, u64)>, String> {
+    let has_sparse_records = records.contains_key("GNU.sparse.major")
+        || records.contains_key("GNU.sparse.map")
+        || records.contains_key("GNU.sparse.offset.0");
+    if !has_sparse_records {
+        return Ok(None);
+    }
+
+    let real_size = pax_real_size(records)?;
+
+    if let Some(map) = records.get("GNU.sparse.map") {
+        let numbers = map
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                s.parse::<u64>()
+                    .map_err(|_| String::from("invalid GNU.sparse.map record"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let entries = numbers
+            .chunks_exact(2)
+            .map(|pair| SparseEntry {
+                offset: pair[0],
+                length: pair[1],
+            })
+            .collect();
+
+        return Ok(Some((entries, real_size)));
+    }
+
+    let mut entries = Vec::new();
+    let mut index = 0;
+    loop {
+        let offset = records.get(&format!("GNU.sparse.offset.{index}"));
+        let length = records.get(&format!("GNU.sparse.numbytes.{index}"));
+        let (Some(offset), Some(length)) = (offset, length) else {
+            break;
+        };
+
+        entries.push(SparseEntry {
+            offset: offset
+                .parse()
+                .map_err(|_| String::from("invalid GNU.sparse.offset record"))?,
+            length: length
+                .parse()
+                .map_err(|_| String::from("invalid GNU.sparse.numbytes record"))?,
+        });
+        index += 1;
+    }
+
+    if entries.is_empty() {
+        return Err(String::from(
+            "GNU sparse entry has no offset/numbytes records - likely sparse format 1.0, which stores its map inline in the entry data instead",
+        ));
+    }
+
+    Ok(Some((entries, real_size)))
+}
+
+/// Parses a GNU sparse format 1.0 map, which lives at the start of the
+/// entry's own data stream rather than in the header or PAX records: a
+/// decimal entry count, then `offset`/`numbytes` pairs (each newline
+/// terminated), padded with NULs out to the next 512-byte boundary. Returns
+/// the parsed entries and how many bytes of `data` the map (plus padding)
+/// occupied - the real segment data immediately follows.
+pub fn parse_gnu_sparse_1_0_map(data: &[u8]) -> Result<(Vec<SparseEntry>, usize), String> {
+    fn read_decimal_line(data: &[u8], pos: &mut usize) -> Result<u64, String> {
+        let newline = data[*pos..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| String::from("truncated GNU sparse format 1.0 map"))?;
+        let line = std::str::from_utf8(&data[*pos..*pos + newline]).map_err(|e| e.to_string())?;
+        *pos += newline + 1;
+        line.parse()
+            .map_err(|_| String::from("invalid GNU sparse format 1.0 map entry"))
+    }
+
+    let mut pos = 0;
+    let count = read_decimal_line(data, &mut pos)?;
+
+    let mut entries = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let offset = read_decimal_line(data, &mut pos)?;
+        let length = read_decimal_line(data, &mut pos)?;
+        entries.push(SparseEntry { offset, length });
+    }
+
+    let padded_len = (pos + 511) / 512 * 512;
+    Ok((entries, padded_len))
+}
+
+#[test]
+fn parse_pax_sparse_map_test() {
+    let mut records = HashMap::new();
+    records.insert("GNU.sparse.major".to_owned(), "0".to_owned());
+    records.insert("GNU.sparse.size".to_owned(), "1024".to_owned());
+    records.insert("GNU.sparse.map".to_owned(), "0,10,512,10".to_owned());
+
+    let (entries, real_size) = parse_pax_sparse(&records).unwrap().unwrap();
+    assert_eq!(real_size, 1024);
+    assert_eq!(
+        entries,
+        vec![
+            SparseEntry { offset: 0, length: 10 },
+            SparseEntry {
+                offset: 512,
+                length: 10
+            },
+        ]
+    );
+}
+
+#[test]
+fn parse_pax_sparse_numbered_test() {
+    let mut records = HashMap::new();
+    records.insert("GNU.sparse.realsize".to_owned(), "2048".to_owned());
+    records.insert("GNU.sparse.offset.0".to_owned(), "0".to_owned());
+    records.insert("GNU.sparse.numbytes.0".to_owned(), "5".to_owned());
+    records.insert("GNU.sparse.offset.1".to_owned(), "1024".to_owned());
+    records.insert("GNU.sparse.numbytes.1".to_owned(), "5".to_owned());
+
+    let (entries, real_size) = parse_pax_sparse(&records).unwrap().unwrap();
+    assert_eq!(real_size, 2048);
+    assert_eq!(entries.len(), 2);
+}
+
+#[test]
+fn parse_pax_sparse_none_test() {
+    let records = HashMap::new();
+    assert!(parse_pax_sparse(&records).unwrap().is_none());
+}
+
+#[test]
+fn is_gnu_sparse_1_0_test() {
+    let mut records = HashMap::new();
+    assert!(!is_gnu_sparse_1_0(&records));
+
+    records.insert("GNU.sparse.major".to_owned(), "1".to_owned());
+    records.insert("GNU.sparse.minor".to_owned(), "0".to_owned());
+    assert!(is_gnu_sparse_1_0(&records));
+}
+
+#[test]
+fn parse_pax_sparse_errors_instead_of_silently_dropping_format_1_0_test() {
+    let mut records = HashMap::new();
+    records.insert("GNU.sparse.major".to_owned(), "1".to_owned());
+    records.insert("GNU.sparse.minor".to_owned(), "0".to_owned());
+    records.insert("GNU.sparse.realsize".to_owned(), "1024".to_owned());
+
+    assert!(parse_pax_sparse(&records).is_err());
+}
+
+#[test]
+fn parse_gnu_sparse_1_0_map_test() {
+    let mut map = b"2\n0\n10\n512\n10\n".to_vec();
+    map.resize(512, 0);
+
+    let (entries, map_len) = parse_gnu_sparse_1_0_map(&map).unwrap();
+    assert_eq!(map_len, 512);
+    assert_eq!(
+        entries,
+        vec![
+            SparseEntry { offset: 0, length: 10 },
+            SparseEntry {
+                offset: 512,
+                length: 10
+            },
+        ]
+    );
+}